@@ -0,0 +1,146 @@
+//! Generates the `ZERO_HASHES` lookup table at compile time.
+//!
+//! Computing this at runtime (as the previous `LazyLock`-based version did) costs a heap
+//! allocation and a one-time hashing pass the first time it's touched, and pulls in `std`.
+//! Doing it here instead means `ZERO_HASHES` in `lib.rs` is a plain `static` with no
+//! initialization cost, so it stays usable from `no_std` dependents that only need the table
+//! (the rest of this crate's API still requires `std`).
+
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+/// Keep in sync with `ZERO_HASHES_MAX_INDEX` in `lib.rs`.
+const ZERO_HASHES_MAX_INDEX: usize = 48;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let hashes = compute_zero_hashes();
+
+    let mut src = String::new();
+    src.push_str(
+        "/// Cached zero hashes where `ZERO_HASHES[i]` is the hash of a Merkle tree with 2^i zero leaves.\n",
+    );
+    src.push_str("pub static ZERO_HASHES: [[u8; HASH_LEN]; ZERO_HASHES_MAX_INDEX + 1] = [\n");
+    for hash in &hashes {
+        src.push_str("    [");
+        for byte in hash {
+            let _ = write!(src, "{byte}, ");
+        }
+        src.push_str("],\n");
+    }
+    src.push_str("];\n");
+
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR set by cargo");
+    fs::write(Path::new(&out_dir).join("zero_hashes.rs"), src).expect("write generated table");
+}
+
+/// Computes `ZERO_HASHES[i]`, the root of a Merkle subtree of `2^i` all-zero leaves, for `i`
+/// in `0..=ZERO_HASHES_MAX_INDEX`.
+///
+/// Level 0 is a single all-zero 32-byte chunk; each subsequent level is
+/// `SHA256(previous || previous)`. The hashing itself is a minimal hand-rolled SHA256 rather
+/// than a build-dependency, since this is the only hashing the build script needs to do.
+fn compute_zero_hashes() -> Vec<[u8; 32]> {
+    let mut hashes = vec![[0u8; 32]; ZERO_HASHES_MAX_INDEX + 1];
+
+    for i in 0..ZERO_HASHES_MAX_INDEX {
+        let mut block = [0u8; 64];
+        block[..32].copy_from_slice(&hashes[i]);
+        block[32..].copy_from_slice(&hashes[i]);
+        hashes[i + 1] = sha256(&block);
+    }
+
+    hashes
+}
+
+#[rustfmt::skip]
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const H0: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// A minimal, build-script-only SHA256 of a single 64-byte block (our inputs here, the
+/// all-zero-padded concatenation of two hashes, never need more than one block of padding).
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut state = H0;
+
+    let bit_len = (data.len() as u64) * 8;
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&bit_len.to_be_bytes());
+
+    for block in msg.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in w.iter_mut().take(16).enumerate() {
+            *word = u32::from_be_bytes([
+                block[i * 4],
+                block[i * 4 + 1],
+                block[i * 4 + 2],
+                block[i * 4 + 3],
+            ]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ ((!e) & g);
+            let temp1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+
+        state[0] = state[0].wrapping_add(a);
+        state[1] = state[1].wrapping_add(b);
+        state[2] = state[2].wrapping_add(c);
+        state[3] = state[3].wrapping_add(d);
+        state[4] = state[4].wrapping_add(e);
+        state[5] = state[5].wrapping_add(f);
+        state[6] = state[6].wrapping_add(g);
+        state[7] = state[7].wrapping_add(h);
+    }
+
+    let mut out = [0u8; 32];
+    for (chunk, word) in out.chunks_exact_mut(4).zip(state.iter()) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    out
+}
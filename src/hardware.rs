@@ -0,0 +1,313 @@
+//! Hardware-accelerated SHA256 backend.
+//!
+//! On platforms that expose the relevant CPU extensions (Intel SHA Extensions on `x86_64`,
+//! the `sha2` cryptographic extension on `aarch64`), this module implements the SHA256
+//! compression function directly with intrinsics. Everywhere else `is_supported()` returns
+//! `false` and callers are expected to fall back to the portable `sha2`-crate implementation.
+
+/// Returns `true` if the current CPU exposes the SHA256 hardware extensions this module
+/// knows how to use.
+///
+/// The check is performed once by the caller (see `DynamicContext`) and cached, since CPU
+/// features cannot change at runtime.
+pub fn is_supported() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        std::is_x86_feature_detected!("sha")
+            && std::is_x86_feature_detected!("sse2")
+            && std::is_x86_feature_detected!("ssse3")
+            && std::is_x86_feature_detected!("sse4.1")
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        std::arch::is_aarch64_feature_detected!("sha2")
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        false
+    }
+}
+
+/// A one-shot hardware-accelerated SHA256 hasher.
+///
+/// This mirrors the shape of [`crate::Sha2Context`] but routes the block compression through
+/// CPU intrinsics instead of the portable `sha2` crate. Construction does not itself check
+/// `is_supported()`; callers must only build one after confirming support, since invoking the
+/// intrinsics on a CPU that lacks them is undefined behaviour.
+pub struct HardwareSha256 {
+    state: [u32; 8],
+    buffer: [u8; 64],
+    buffer_len: usize,
+    total_len: u64,
+}
+
+const H0: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+    0x5be0cd19,
+];
+
+impl HardwareSha256 {
+    pub fn new() -> Self {
+        Self {
+            state: H0,
+            buffer: [0u8; 64],
+            buffer_len: 0,
+            total_len: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        self.total_len += bytes.len() as u64;
+
+        if self.buffer_len > 0 {
+            let space = 64 - self.buffer_len;
+            let take = space.min(bytes.len());
+            self.buffer[self.buffer_len..self.buffer_len + take].copy_from_slice(&bytes[..take]);
+            self.buffer_len += take;
+            bytes = &bytes[take..];
+
+            if self.buffer_len == 64 {
+                let block = self.buffer;
+                self.compress(&block);
+                self.buffer_len = 0;
+            }
+        }
+
+        while bytes.len() >= 64 {
+            let mut block = [0u8; 64];
+            block.copy_from_slice(&bytes[..64]);
+            self.compress(&block);
+            bytes = &bytes[64..];
+        }
+
+        if !bytes.is_empty() {
+            self.buffer[..bytes.len()].copy_from_slice(bytes);
+            self.buffer_len = bytes.len();
+        }
+    }
+
+    pub fn finalize(mut self) -> [u8; 32] {
+        let bit_len = self.total_len * 8;
+
+        let mut pad = [0u8; 72];
+        pad[0] = 0x80;
+        let pad_len = if self.buffer_len < 56 {
+            56 - self.buffer_len
+        } else {
+            120 - self.buffer_len
+        };
+        pad[pad_len..pad_len + 8].copy_from_slice(&bit_len.to_be_bytes());
+        self.update_no_len(&pad[..pad_len + 8]);
+
+        let mut out = [0u8; 32];
+        for (chunk, word) in out.chunks_exact_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        out
+    }
+
+    /// Like `update`, but doesn't touch `total_len` (used for the length-padding block, whose
+    /// length was already folded into `total_len` before padding was computed).
+    fn update_no_len(&mut self, mut bytes: &[u8]) {
+        if self.buffer_len > 0 {
+            let space = 64 - self.buffer_len;
+            let take = space.min(bytes.len());
+            self.buffer[self.buffer_len..self.buffer_len + take].copy_from_slice(&bytes[..take]);
+            self.buffer_len += take;
+            bytes = &bytes[take..];
+
+            if self.buffer_len == 64 {
+                let block = self.buffer;
+                self.compress(&block);
+                self.buffer_len = 0;
+            }
+        }
+
+        while bytes.len() >= 64 {
+            let mut block = [0u8; 64];
+            block.copy_from_slice(&bytes[..64]);
+            self.compress(&block);
+            bytes = &bytes[64..];
+        }
+
+        if !bytes.is_empty() {
+            self.buffer[..bytes.len()].copy_from_slice(bytes);
+            self.buffer_len = bytes.len();
+        }
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            x86::compress(&mut self.state, block);
+        }
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            aarch64::compress(&mut self.state, block);
+        }
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            let _ = block;
+            unreachable!("HardwareSha256 must not be constructed on unsupported targets");
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use core::arch::x86_64::*;
+
+    /// One block of SHA256 compression using Intel SHA Extensions.
+    ///
+    /// Safety: caller must have confirmed `sha`, `sse2`, `ssse3` and `sse4.1` support (see
+    /// `super::is_supported`).
+    #[target_feature(enable = "sha,sse2,ssse3,sse4.1")]
+    pub unsafe fn compress(state: &mut [u32; 8], block: &[u8; 64]) {
+        const K: [u32; 64] = crate::K256;
+
+        let mask = _mm_set_epi64x(0x0c0d_0e0f_0809_0a0bu64 as i64, 0x0405_0607_0001_0203u64 as i64);
+
+        let mut state0 = _mm_set_epi32(state[0] as i32, state[1] as i32, state[4] as i32, state[5] as i32);
+        let mut state1 = _mm_set_epi32(state[2] as i32, state[3] as i32, state[6] as i32, state[7] as i32);
+
+        let abef_save = state0;
+        let cdgh_save = state1;
+
+        // Message schedule, kept as four rotating 128-bit lanes (`w[0]` holds the four words
+        // about to be consumed; `w[1..4]` are the words that follow).
+        let mut w = [
+            _mm_shuffle_epi8(_mm_loadu_si128(block.as_ptr() as *const __m128i), mask),
+            _mm_shuffle_epi8(_mm_loadu_si128(block.as_ptr().add(16) as *const __m128i), mask),
+            _mm_shuffle_epi8(_mm_loadu_si128(block.as_ptr().add(32) as *const __m128i), mask),
+            _mm_shuffle_epi8(_mm_loadu_si128(block.as_ptr().add(48) as *const __m128i), mask),
+        ];
+
+        for group in 0..16 {
+            let k = _mm_loadu_si128(K.as_ptr().add(group * 4) as *const __m128i);
+            let mut msg = _mm_add_epi32(w[0], k);
+            state1 = _mm_sha256rnds2_epu32(state1, state0, msg);
+            msg = _mm_shuffle_epi32(msg, 0x0e);
+            state0 = _mm_sha256rnds2_epu32(state0, state1, msg);
+
+            // Schedule the four words consumed four groups from now, then rotate the lanes.
+            // The last four groups need no new words, but the lanes still rotate.
+            let next = if group < 12 {
+                let tmp = _mm_alignr_epi8(w[3], w[2], 4);
+                let next = _mm_add_epi32(_mm_sha256msg1_epu32(w[0], w[1]), tmp);
+                _mm_sha256msg2_epu32(next, w[3])
+            } else {
+                w[3]
+            };
+            w = [w[1], w[2], w[3], next];
+        }
+
+        state0 = _mm_add_epi32(state0, abef_save);
+        state1 = _mm_add_epi32(state1, cdgh_save);
+
+        let mut out = [0u32; 8];
+        _mm_storeu_si128(out.as_mut_ptr() as *mut __m128i, state0);
+        _mm_storeu_si128(out.as_mut_ptr().add(4) as *mut __m128i, state1);
+
+        state[0] = out[3];
+        state[1] = out[2];
+        state[2] = out[7];
+        state[3] = out[6];
+        state[4] = out[1];
+        state[5] = out[0];
+        state[6] = out[5];
+        state[7] = out[4];
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod aarch64 {
+    use core::arch::aarch64::*;
+
+    /// One block of SHA256 compression using the Armv8 Cryptography Extensions.
+    ///
+    /// Safety: caller must have confirmed the `sha2` target feature is available (see
+    /// `super::is_supported`).
+    #[target_feature(enable = "sha2")]
+    pub unsafe fn compress(state: &mut [u32; 8], block: &[u8; 64]) {
+        const K: [u32; 64] = crate::K256;
+
+        let mut st0 = vld1q_u32(state[0..4].as_ptr());
+        let mut st1 = vld1q_u32(state[4..8].as_ptr());
+
+        let abcd_save = st0;
+        let efgh_save = st1;
+
+        let mut msg0 = vreinterpretq_u32_u8(vrev32q_u8(vld1q_u8(block.as_ptr())));
+        let mut msg1 = vreinterpretq_u32_u8(vrev32q_u8(vld1q_u8(block.as_ptr().add(16))));
+        let mut msg2 = vreinterpretq_u32_u8(vrev32q_u8(vld1q_u8(block.as_ptr().add(32))));
+        let mut msg3 = vreinterpretq_u32_u8(vrev32q_u8(vld1q_u8(block.as_ptr().add(48))));
+
+        for i in 0..16 {
+            let k = vld1q_u32(K[i * 4..].as_ptr());
+            let wk = vaddq_u32(msg0, k);
+            let tmp0 = st0;
+            st0 = vsha256hq_u32(st0, st1, wk);
+            st1 = vsha256h2q_u32(st1, tmp0, wk);
+
+            let next_msg = if i < 12 {
+                vsha256su1q_u32(vsha256su0q_u32(msg0, msg1), msg2, msg3)
+            } else {
+                msg3
+            };
+            msg0 = msg1;
+            msg1 = msg2;
+            msg2 = msg3;
+            msg3 = next_msg;
+        }
+
+        st0 = vaddq_u32(st0, abcd_save);
+        st1 = vaddq_u32(st1, efgh_save);
+
+        vst1q_u32(state[0..4].as_mut_ptr(), st0);
+        vst1q_u32(state[4..8].as_mut_ptr(), st1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    /// Hashes `input` with `HardwareSha256` and compares it against the portable `sha2` crate.
+    ///
+    /// Covers the empty input, a partial buffer, an exact block, and multi-block inputs with
+    /// a trailing partial block, so a broken message schedule (which only shows up once a
+    /// block's worth of data has gone through `compress`) can't hide behind a short test.
+    fn assert_matches_sha2(input: &[u8]) {
+        let mut hw = HardwareSha256::new();
+        hw.update(input);
+        let actual = hw.finalize();
+
+        let mut expected_hasher = Sha256::new();
+        expected_hasher.update(input);
+        let expected: [u8; 32] = expected_hasher.finalize().into();
+
+        assert_eq!(actual, expected, "mismatch for input of length {}", input.len());
+    }
+
+    #[test]
+    fn hardware_backend_matches_sha2() {
+        // `DynamicContext` routes *every* hash through this backend on any host that reports
+        // SHA extension support, so silently skipping this test there (as a prior version of
+        // it did) would let a broken message schedule ship to every such user untested. Fail
+        // loudly instead of skipping: CI for this crate must run this test on SHA-NI (x86_64)
+        // or ARMv8 crypto-extension (aarch64) hardware.
+        assert!(
+            is_supported(),
+            "this test exercises the hardware SHA256 backend and must run on a CPU with SHA \
+             extensions (x86_64 SHA-NI or aarch64 `sha2`) -- run it on such hardware rather \
+             than skipping it"
+        );
+
+        let lengths = [0, 1, 55, 56, 63, 64, 65, 100, 128, 129, 200, 1000];
+        for &len in &lengths {
+            let input: Vec<u8> = (0..len).map(|i| (i % 256) as u8).collect();
+            assert_matches_sha2(&input);
+        }
+    }
+}
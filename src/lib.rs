@@ -7,33 +7,141 @@
 //! Now this crate serves primarily as a wrapper over the `sha2` crate.
 
 use sha2::{Digest, Sha256};
-
-#[cfg(feature = "zero_hash_cache")]
 use std::sync::LazyLock;
 
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
+mod hardware;
+mod merkle;
+
+pub use merkle::{generate_proof, merkleize, verify_proof, TreeHashCache};
+
+/// SHA256 round constants, used by the hardware-accelerated compression functions in
+/// [`hardware`] (the portable path goes through the `sha2` crate, which has its own copy).
+///
+/// Only defined on the architectures `hardware` has an intrinsics backend for; elsewhere
+/// nothing references it.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[rustfmt::skip]
+pub(crate) const K256: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
 /// Length of a SHA256 hash in bytes.
 pub const HASH_LEN: usize = 32;
 
-/// Returns the digest of `input` using the `sha2` implementation.
+/// Which SHA256 implementation `DynamicContext` dispatches to.
+///
+/// Chosen once, the first time it's needed, based on the CPU features actually available;
+/// see [`DynamicContext::backend`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Backend {
+    /// The portable `sha2` crate implementation.
+    Software,
+    /// Hand-rolled compression using the CPU's SHA extensions (SHA-NI on `x86_64`, the
+    /// cryptography extension on `aarch64`).
+    Hardware,
+}
+
+/// The backend selected for this process, detected once on first use and cached for the
+/// lifetime of the program (CPU features cannot change at runtime).
+static BACKEND: LazyLock<Backend> = LazyLock::new(|| {
+    if hardware::is_supported() {
+        Backend::Hardware
+    } else {
+        Backend::Software
+    }
+});
+
+/// A SHA256 hasher that transparently dispatches to the fastest backend the host CPU
+/// supports.
+///
+/// This is what `hash`, `hash_fixed` and `hash32_concat` use internally; most callers should
+/// reach for those free functions instead of constructing a context directly.
+pub struct DynamicContext {
+    backend: Backend,
+    software: Option<Sha2Context>,
+    hardware: Option<hardware::HardwareSha256>,
+}
+
+impl Sha256Context for DynamicContext {
+    fn new() -> Self {
+        match *BACKEND {
+            Backend::Software => Self {
+                backend: Backend::Software,
+                software: Some(Sha2Context::new()),
+                hardware: None,
+            },
+            Backend::Hardware => Self {
+                backend: Backend::Hardware,
+                software: None,
+                hardware: Some(hardware::HardwareSha256::new()),
+            },
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        match self.backend {
+            Backend::Software => self.software.as_mut().unwrap().update(bytes),
+            Backend::Hardware => self.hardware.as_mut().unwrap().update(bytes),
+        }
+    }
+
+    fn finalize(self) -> [u8; HASH_LEN] {
+        match self.backend {
+            Backend::Software => self.software.unwrap().finalize(),
+            Backend::Hardware => self.hardware.unwrap().finalize(),
+        }
+    }
+}
+
+/// Returns the digest of `input`, using the hardware-accelerated backend when the host CPU
+/// supports it and falling back to the `sha2` crate otherwise.
 pub fn hash(input: &[u8]) -> Vec<u8> {
-    Sha2Impl.hash(input)
+    hash_fixed(input).to_vec()
 }
 
 /// Hash function returning a fixed-size array (to save on allocations).
 ///
-/// Uses the `sha2` implementation.
+/// See [`hash`] for which backend is used.
 pub fn hash_fixed(input: &[u8]) -> [u8; HASH_LEN] {
-    Sha2Impl.hash_fixed(input)
+    let mut ctxt = DynamicContext::new();
+    ctxt.update(input);
+    ctxt.finalize()
 }
 
 /// Compute the hash of two slices concatenated.
 pub fn hash32_concat(h1: &[u8], h2: &[u8]) -> [u8; HASH_LEN] {
-    let mut ctxt = Sha2Context::new();
-    ctxt.update(h1);
-    ctxt.update(h2);
+    hash_concat(&[h1, h2])
+}
+
+/// Compute the hash of any number of slices concatenated, without allocating an intermediate
+/// buffer to join them.
+pub fn hash_concat(parts: &[&[u8]]) -> [u8; HASH_LEN] {
+    let mut ctxt = DynamicContext::new();
+    for part in parts {
+        ctxt.update(part);
+    }
     ctxt.finalize()
 }
 
+/// Computes `SHA256(SHA256(input))` in one call.
+///
+/// Double-SHA256 is ubiquitous in blockchain contexts; doing it through two separate calls to
+/// [`hash`] would waste an allocation on the intermediate digest.
+pub fn hash256d(input: &[u8]) -> [u8; HASH_LEN] {
+    let first = hash_fixed(input);
+    hash_fixed(&first)
+}
+
 /// Context trait for abstracting over implementation contexts.
 pub trait Sha256Context {
     fn new() -> Self;
@@ -48,6 +156,35 @@ pub struct Sha2Context {
     hasher: Sha256,
 }
 
+/// Wipes the wrapped hasher's state on drop, so fragments of whatever was hashed (e.g.
+/// secret-key-derived preimages) don't linger in memory.
+///
+/// The `sha2` crate doesn't expose `Sha256`'s internal block buffer or chaining state for us
+/// to zero field-by-field (and doesn't implement `Zeroize` itself), so instead we reach into
+/// `self.hasher` through a raw byte view of the whole struct and overwrite it in place.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Sha2Context {
+    fn zeroize(&mut self) {
+        let bytes = unsafe {
+            std::slice::from_raw_parts_mut(
+                &mut self.hasher as *mut Sha256 as *mut u8,
+                std::mem::size_of::<Sha256>(),
+            )
+        };
+        bytes.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for Sha2Context {}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Sha2Context {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 impl Sha256Context for Sha2Context {
     fn new() -> Self {
         Self {
@@ -59,8 +196,22 @@ impl Sha256Context for Sha2Context {
         self.hasher.update(bytes);
     }
 
-    fn finalize(self) -> [u8; HASH_LEN] {
+    fn finalize(mut self) -> [u8; HASH_LEN] {
+        // Plain `finalize(self)` consumes `self.hasher` and drops it without scrubbing --
+        // under the `zeroize` feature that would leave the real, secret-bearing state behind
+        // even though `Drop` looks like it's handling cleanup (it would only ever see an
+        // already-fresh placeholder swapped in ahead of it). Use `finalize_reset`, which
+        // takes `&mut self.hasher` and hands the digest back without consuming the field,
+        // then explicitly wipe `self.hasher`'s raw bytes ourselves.
+        #[cfg(feature = "zeroize")]
+        let result = {
+            let digest = self.hasher.finalize_reset();
+            self.zeroize();
+            digest
+        };
+        #[cfg(not(feature = "zeroize"))]
         let result = self.hasher.finalize();
+
         let mut output = [0u8; HASH_LEN];
         output.copy_from_slice(&result);
         output
@@ -102,17 +253,11 @@ impl Sha256Trait for Sha2Impl {
 #[cfg(feature = "zero_hash_cache")]
 pub const ZERO_HASHES_MAX_INDEX: usize = 48;
 
+// Generated by `build.rs`: defines `pub static ZERO_HASHES: [[u8; HASH_LEN]; ZERO_HASHES_MAX_INDEX + 1]`.
+// Computed at compile time so there's no allocation or lazy-init cost, and so the table is
+// usable from `no_std` dependents that only need `ZERO_HASHES` itself.
 #[cfg(feature = "zero_hash_cache")]
-/// Cached zero hashes where `ZERO_HASHES[i]` is the hash of a Merkle tree with 2^i zero leaves.
-pub static ZERO_HASHES: LazyLock<Vec<[u8; HASH_LEN]>> = LazyLock::new(|| {
-    let mut hashes = vec![[0; HASH_LEN]; ZERO_HASHES_MAX_INDEX + 1];
-
-    for i in 0..ZERO_HASHES_MAX_INDEX {
-        hashes[i + 1] = hash32_concat(&hashes[i], &hashes[i]);
-    }
-
-    hashes
-});
+include!(concat!(env!("OUT_DIR"), "/zero_hashes.rs"));
 
 #[cfg(test)]
 mod tests {
@@ -129,6 +274,68 @@ mod tests {
         assert_eq!(expected, output);
     }
 
+    #[test]
+    fn hash256d_is_double_hash() {
+        let input = b"hello world";
+        assert_eq!(hash256d(input), hash_fixed(&hash_fixed(input)));
+    }
+
+    #[test]
+    fn hash_concat_matches_hash32_concat() {
+        let (h1, h2) = ([1u8; HASH_LEN], [2u8; HASH_LEN]);
+        assert_eq!(hash_concat(&[&h1, &h2]), hash32_concat(&h1, &h2));
+    }
+
+    #[cfg(feature = "zeroize")]
+    mod zeroize_context {
+        use super::*;
+
+        #[test]
+        fn context_still_hashes_correctly_under_zeroize() {
+            let mut ctxt = Sha2Context::new();
+            ctxt.update(b"hello world");
+            let output = ctxt.finalize();
+
+            assert_eq!(output, hash_fixed(b"hello world"));
+        }
+
+        /// A raw byte view over `ctxt`'s wrapped hasher, so the test can read back the exact
+        /// memory `zeroize()` is supposed to scrub.
+        fn hasher_bytes(ctxt: &Sha2Context) -> &[u8] {
+            unsafe {
+                std::slice::from_raw_parts(
+                    &ctxt.hasher as *const Sha256 as *const u8,
+                    std::mem::size_of::<Sha256>(),
+                )
+            }
+        }
+
+        #[test]
+        fn zeroize_wipes_the_wrapped_hasher() {
+            let secret = b"some secret-derived preimage!!!";
+            let mut ctxt = Sha2Context::new();
+            ctxt.update(secret);
+
+            // `update` with an input shorter than a block leaves it sitting verbatim in the
+            // hasher's pending-block buffer, so we can confirm the secret is actually present
+            // in memory before wiping -- otherwise this test would pass even if `zeroize`
+            // were a no-op.
+            let before = hasher_bytes(&ctxt).to_vec();
+            assert!(
+                before.windows(secret.len()).any(|w| w == secret),
+                "preimage should be present in the hasher's buffer before zeroizing"
+            );
+
+            ctxt.zeroize();
+
+            let after = hasher_bytes(&ctxt);
+            assert!(
+                after.iter().all(|&b| b == 0),
+                "hasher bytes should be all-zero after zeroize()"
+            );
+        }
+    }
+
     #[cfg(feature = "zero_hash_cache")]
     mod zero_hash {
         use super::*;
@@ -0,0 +1,327 @@
+//! Merkleization of fixed-size chunks, as used throughout beacon-chain state hashing.
+
+use crate::{hash32_concat, HASH_LEN};
+
+#[cfg(feature = "zero_hash_cache")]
+use crate::ZERO_HASHES;
+
+/// Returns the zero hash for a subtree with `2^depth` zero leaves.
+///
+/// Falls back to computing it on the fly when the `zero_hash_cache` feature (and therefore
+/// `ZERO_HASHES`) isn't available.
+fn zero_hash(depth: usize) -> [u8; HASH_LEN] {
+    #[cfg(feature = "zero_hash_cache")]
+    {
+        ZERO_HASHES[depth]
+    }
+
+    #[cfg(not(feature = "zero_hash_cache"))]
+    {
+        let mut hash = [0u8; HASH_LEN];
+        for _ in 0..depth {
+            hash = hash32_concat(&hash, &hash);
+        }
+        hash
+    }
+}
+
+/// The number of levels needed to hold `leaf_count` leaves: `0` for `0` or `1` leaves, and
+/// `ceil(log2(leaf_count))` otherwise.
+///
+/// Computed with integer bit-twiddling rather than `f64::log2` -- besides being out of place
+/// in an otherwise integer-only crate, a power-of-two `leaf_count` landing a hair below its
+/// true `log2` to floating-point error would silently add a spurious tree level and change
+/// the root.
+fn tree_depth(leaf_count: usize) -> usize {
+    leaf_count.max(1).next_power_of_two().trailing_zeros() as usize
+}
+
+/// Pairs up adjacent nodes of `level` (the layer at `depth` levels above the leaves) via
+/// `hash32_concat`, producing the layer above it.
+///
+/// A missing right-hand sibling -- `level` has an odd length, or is empty outright -- is
+/// filled in from `ZERO_HASHES`, so absent subtrees cost nothing to account for. Used by
+/// `merkleize`, `TreeHashCache::new` and `generate_proof`, which all build layers the same
+/// way.
+fn hash_level(level: &[[u8; HASH_LEN]], depth: usize) -> Vec<[u8; HASH_LEN]> {
+    if level.is_empty() {
+        return vec![zero_hash(depth + 1)];
+    }
+
+    let mut next = Vec::with_capacity(level.len().div_ceil(2));
+    let mut i = 0;
+    while i < level.len() {
+        let left = level[i];
+        let right = level.get(i + 1).copied().unwrap_or_else(|| zero_hash(depth));
+        next.push(hash32_concat(&left, &right));
+        i += 2;
+    }
+    next
+}
+
+/// Merkleizes `chunks`, padding with zero-subtree hashes so the tree is at least `min_depth`
+/// levels deep.
+///
+/// This mirrors the SSZ Merkleization rule: chunks are paired up and hashed level by level,
+/// and any missing right-hand sibling -- whether because a level has an odd number of nodes,
+/// or because the whole level doesn't exist yet -- is filled in with the appropriate
+/// `ZERO_HASHES` entry rather than actually hashed, so absent subtrees cost nothing.
+pub fn merkleize(chunks: &[[u8; HASH_LEN]], min_depth: usize) -> [u8; HASH_LEN] {
+    if chunks.is_empty() {
+        return zero_hash(min_depth);
+    }
+
+    let depth = tree_depth(chunks.len()).max(min_depth);
+
+    let mut level = chunks.to_vec();
+    for d in 0..depth {
+        level = hash_level(&level, d);
+    }
+
+    level[0]
+}
+
+/// Incremental tree-hash cache.
+///
+/// Holds every intermediate layer of a Merkle tree built over a fixed set of leaves, so that
+/// re-hashing after mutating a handful of leaves only walks the dirty path(s) from those
+/// leaves to the root instead of rebuilding the whole tree. `layers[0]` is the leaf layer,
+/// and each subsequent layer is half the length of the one below (padded with zero hashes as
+/// `merkleize` does).
+pub struct TreeHashCache {
+    layers: Vec<Vec<[u8; HASH_LEN]>>,
+    dirty: Vec<bool>,
+}
+
+impl TreeHashCache {
+    /// Builds a fresh cache over `leaves`, padded to `min_depth`, with every leaf marked
+    /// clean (the layers already reflect `leaves` as given).
+    pub fn new(leaves: &[[u8; HASH_LEN]], min_depth: usize) -> Self {
+        if leaves.is_empty() {
+            // With no leaves and no pairing rounds to run (e.g. `min_depth == 0`), there's
+            // nothing to derive a root layer from; mirror `merkleize`'s empty-chunks case by
+            // seeding the tree directly with the zero-subtree root instead.
+            return Self {
+                layers: vec![Vec::new(), vec![zero_hash(min_depth)]],
+                dirty: Vec::new(),
+            };
+        }
+
+        let depth = tree_depth(leaves.len()).max(min_depth);
+
+        let mut layers = Vec::with_capacity(depth + 1);
+        layers.push(leaves.to_vec());
+
+        let mut level = leaves.to_vec();
+        for d in 0..depth {
+            level = hash_level(&level, d);
+            layers.push(level.clone());
+        }
+
+        Self {
+            dirty: vec![false; layers[0].len()],
+            layers,
+        }
+    }
+
+    /// Overwrites the leaf at `index` and marks it dirty, to be folded into the root on the
+    /// next `recalculate()`.
+    pub fn set_leaf(&mut self, index: usize, leaf: [u8; HASH_LEN]) {
+        self.layers[0][index] = leaf;
+        self.dirty[index] = true;
+    }
+
+    /// Recomputes every node on the path from a dirty leaf to the root, leaving clean nodes
+    /// untouched, and returns the (possibly unchanged) root.
+    pub fn recalculate(&mut self) -> [u8; HASH_LEN] {
+        let mut dirty_indices: Vec<usize> = self
+            .dirty
+            .iter()
+            .enumerate()
+            .filter(|(_, &d)| d)
+            .map(|(i, _)| i)
+            .collect();
+        self.dirty.iter_mut().for_each(|d| *d = false);
+
+        for depth in 0..self.layers.len() - 1 {
+            if dirty_indices.is_empty() {
+                break;
+            }
+
+            let mut parents: Vec<usize> = dirty_indices.iter().map(|i| i / 2).collect();
+            parents.sort_unstable();
+            parents.dedup();
+
+            for &parent in &parents {
+                let left_idx = parent * 2;
+                let right_idx = left_idx + 1;
+
+                let left = self.layers[depth][left_idx];
+                let right = self.layers[depth]
+                    .get(right_idx)
+                    .copied()
+                    .unwrap_or_else(|| zero_hash(depth));
+
+                self.layers[depth + 1][parent] = hash32_concat(&left, &right);
+            }
+
+            dirty_indices = parents;
+        }
+
+        self.layers[self.layers.len() - 1][0]
+    }
+
+    /// The current root, as of the last `recalculate()` (or construction, if nothing has been
+    /// changed since).
+    pub fn root(&self) -> [u8; HASH_LEN] {
+        self.layers[self.layers.len() - 1][0]
+    }
+}
+
+/// Generates a Merkle inclusion proof for `leaves[index]` against a tree of the given
+/// `depth`, returning `(root, proof)` where `proof[d]` is the sibling hash needed at level
+/// `d` on the way from the leaf up to the root.
+///
+/// Absent siblings -- whether because `leaves` is shorter than a full level, or because a
+/// whole subtree wasn't provided at all -- are filled in from `ZERO_HASHES`, so the proof
+/// verifies correctly even over a sparse or partially-filled tree.
+pub fn generate_proof(
+    leaves: &[[u8; HASH_LEN]],
+    index: usize,
+    depth: usize,
+) -> ([u8; HASH_LEN], Vec<[u8; HASH_LEN]>) {
+    let mut level = leaves.to_vec();
+    let mut idx = index;
+    let mut proof = Vec::with_capacity(depth);
+
+    for d in 0..depth {
+        let sibling_idx = idx ^ 1;
+        let sibling = level.get(sibling_idx).copied().unwrap_or_else(|| zero_hash(d));
+        proof.push(sibling);
+
+        level = hash_level(&level, d);
+        idx /= 2;
+    }
+
+    (level[0], proof)
+}
+
+/// Verifies that `leaf` is present at `index` in a tree of the given `depth` whose root is
+/// `root`, given the sibling hashes in `proof` (as returned by `generate_proof`).
+///
+/// Folds the leaf upward one level at a time: at each level, the current index's low bit
+/// says whether `node` is the left (`0`) or right (`1`) child of its parent, which decides
+/// the argument order passed to `hash32_concat`.
+pub fn verify_proof(
+    leaf: [u8; HASH_LEN],
+    proof: &[[u8; HASH_LEN]],
+    depth: usize,
+    index: usize,
+    root: [u8; HASH_LEN],
+) -> bool {
+    if proof.len() != depth {
+        return false;
+    }
+
+    let mut node = leaf;
+    let mut idx = index;
+
+    for sibling in proof {
+        node = if idx & 1 == 0 {
+            hash32_concat(&node, sibling)
+        } else {
+            hash32_concat(sibling, &node)
+        };
+        idx >>= 1;
+    }
+
+    node == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merkleize_single_chunk_is_identity() {
+        let chunk = [1u8; HASH_LEN];
+        assert_eq!(merkleize(&[chunk], 0), chunk);
+    }
+
+    #[test]
+    fn merkleize_pads_odd_level_with_zero_hash() {
+        let chunks = vec![[1u8; HASH_LEN], [2u8; HASH_LEN], [3u8; HASH_LEN]];
+        let expected = {
+            let pair01 = hash32_concat(&chunks[0], &chunks[1]);
+            let pair23 = hash32_concat(&chunks[2], &zero_hash(0));
+            hash32_concat(&pair01, &pair23)
+        };
+        assert_eq!(merkleize(&chunks, 0), expected);
+    }
+
+    #[test]
+    fn merkleize_respects_min_depth() {
+        let chunk = [1u8; HASH_LEN];
+        let depth1 = hash32_concat(&chunk, &zero_hash(0));
+        assert_eq!(merkleize(&[chunk], 1), depth1);
+    }
+
+    #[test]
+    fn generated_proof_verifies() {
+        let leaves = vec![[1u8; HASH_LEN], [2u8; HASH_LEN], [3u8; HASH_LEN], [4u8; HASH_LEN]];
+        let (root, proof) = generate_proof(&leaves, 2, 2);
+
+        assert_eq!(root, merkleize(&leaves, 2));
+        assert!(verify_proof(leaves[2], &proof, 2, 2, root));
+    }
+
+    #[test]
+    fn proof_over_sparse_tree_verifies() {
+        let leaves = vec![[1u8; HASH_LEN]];
+        let (root, proof) = generate_proof(&leaves, 0, 3);
+
+        assert!(verify_proof(leaves[0], &proof, 3, 0, root));
+    }
+
+    #[test]
+    fn tampered_proof_fails_verification() {
+        let leaves = vec![[1u8; HASH_LEN], [2u8; HASH_LEN]];
+        let (root, mut proof) = generate_proof(&leaves, 0, 1);
+        proof[0] = [0xff; HASH_LEN];
+
+        assert!(!verify_proof(leaves[0], &proof, 1, 0, root));
+    }
+
+    #[test]
+    fn tree_hash_cache_handles_empty_leaves() {
+        let mut cache = TreeHashCache::new(&[], 0);
+        assert_eq!(cache.root(), merkleize(&[], 0));
+        assert_eq!(cache.recalculate(), merkleize(&[], 0));
+
+        let cache = TreeHashCache::new(&[], 3);
+        assert_eq!(cache.root(), merkleize(&[], 3));
+    }
+
+    #[test]
+    fn tree_depth_matches_log2_ceil() {
+        assert_eq!(tree_depth(0), 0);
+        assert_eq!(tree_depth(1), 0);
+        assert_eq!(tree_depth(2), 1);
+        assert_eq!(tree_depth(3), 2);
+        assert_eq!(tree_depth(4), 2);
+        assert_eq!(tree_depth(5), 3);
+    }
+
+    #[test]
+    fn tree_hash_cache_matches_merkleize_after_mutation() {
+        let leaves = vec![[1u8; HASH_LEN], [2u8; HASH_LEN], [3u8; HASH_LEN], [4u8; HASH_LEN]];
+        let mut cache = TreeHashCache::new(&leaves, 0);
+        assert_eq!(cache.recalculate(), merkleize(&leaves, 0));
+
+        let mut mutated = leaves.clone();
+        mutated[2] = [9u8; HASH_LEN];
+        cache.set_leaf(2, [9u8; HASH_LEN]);
+
+        assert_eq!(cache.recalculate(), merkleize(&mutated, 0));
+    }
+}